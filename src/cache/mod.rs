@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::hash::Hash;
-use std::ops::Add;
+use std::ops::{Add, Sub};
 use std::time;
 
 pub struct ValueWithTtl<T, C = SystemClock>
@@ -43,11 +43,22 @@ where
             Some(self.value)
         }
     }
+
+    /// Time remaining before this entry expires, or `None` if it already has.
+    pub fn ttl_remaining(&self) -> Option<C::Duration> {
+        if self.is_expired() {
+            None
+        } else {
+            Some(self.deadline - self.clock.now())
+        }
+    }
 }
 
 pub trait Clock {
     type Duration;
-    type Instant: Add<Self::Duration, Output = Self::Instant> + PartialOrd;
+    type Instant: Add<Self::Duration, Output = Self::Instant>
+        + Sub<Self::Instant, Output = Self::Duration>
+        + PartialOrd;
     fn now(&self) -> Self::Instant;
 }
 
@@ -106,6 +117,12 @@ where
         self.inner.get(&key)?.get()
     }
 
+    /// Time remaining before `key`'s entry expires, or `None` if it's absent
+    /// or already expired.
+    pub fn ttl_remaining(&self, key: &K) -> Option<C::Duration> {
+        self.inner.get(key)?.ttl_remaining()
+    }
+
     pub fn gc(&mut self) {
         self.inner.retain(|_, v| !v.is_expired());
     }
@@ -5,22 +5,127 @@ use anyhow::Result;
 
 #[derive(Default, Debug, Deserialize)]
 pub struct ServerConfig {
-    pub bind_addresses: Vec<String>,
+    #[serde(default)]
+    pub bind_addresses: Vec<BindAddress>,
+    #[serde(default)]
+    pub upstreams: Vec<UpstreamConfig>,
+    /// How `upstreams` is queried when it has more than one entry.
+    #[serde(default)]
+    pub mode: UpstreamsMode,
+    #[serde(default)]
+    pub routes: Vec<RouteRule>,
     pub trace: Option<TraceConfig>,
 }
 
-#[derive(Default, Debug, Deserialize)]
+/// Whether a multi-upstream group is queried one at a time or all at once.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UpstreamsMode {
+    Sequential,
+    Race,
+}
+
+impl Default for UpstreamsMode {
+    fn default() -> Self {
+        UpstreamsMode::Sequential
+    }
+}
+
+#[derive(Default, Debug, Deserialize, Clone)]
 pub struct TraceConfig {
     pub service_name: String,
     pub agent_endpoint: String,
 }
 
+/// The protocol a listener speaks on its bound socket.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BindProtocol {
+    Udp,
+    Tcp,
+    Tls,
+}
+
+fn default_bind_timeout_secs() -> u64 {
+    10
+}
+
+/// A single listener: which protocol to speak, which address to bind, and
+/// how long to wait on a client connection/request before giving up.
+#[derive(Debug, Deserialize, Clone)]
+pub struct BindAddress {
+    pub protocol: BindProtocol,
+    pub addr: String,
+    #[serde(default = "default_bind_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+/// Binds both UDP and TCP on `0.0.0.0:5353` so the proxy answers ordinary
+/// DNS clients out of the box when `bind_addresses` is left unspecified.
+pub fn default_bind_addresses() -> Vec<BindAddress> {
+    vec![
+        BindAddress {
+            protocol: BindProtocol::Udp,
+            addr: "0.0.0.0:5353".to_string(),
+            timeout_secs: default_bind_timeout_secs(),
+        },
+        BindAddress {
+            protocol: BindProtocol::Tcp,
+            addr: "0.0.0.0:5353".to_string(),
+            timeout_secs: default_bind_timeout_secs(),
+        },
+    ]
+}
+
+/// The wire transport a configured upstream speaks.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UpstreamKind {
+    Udp,
+    Tls,
+    Https,
+}
+
+impl Default for UpstreamKind {
+    fn default() -> Self {
+        UpstreamKind::Udp
+    }
+}
+
+/// A single upstream resolver, e.g. plain UDP, DNS-over-TLS or DNS-over-HTTPS.
+///
+/// `tls_name` is required for the `tls` and `https` kinds and `doh_path` is
+/// only meaningful for `https`; `ca_file` and `alpn` are optional overrides for both.
+#[derive(Default, Debug, Deserialize, Clone)]
+pub struct UpstreamConfig {
+    #[serde(default)]
+    pub kind: UpstreamKind,
+    pub addr: String,
+    pub tls_name: Option<String>,
+    pub doh_path: Option<String>,
+    pub ca_file: Option<String>,
+    pub alpn: Option<Vec<String>>,
+}
+
+/// A split-horizon / conditional-forwarding rule: queries whose name falls
+/// under `suffix` (e.g. `internal.corp`) are sent to `upstream` instead of
+/// the default upstream chain.
+#[derive(Debug, Deserialize, Clone)]
+pub struct RouteRule {
+    pub suffix: String,
+    pub upstream: UpstreamConfig,
+}
+
 impl ServerConfig {
     pub fn new<P: AsRef<str>>(path: P) -> Result<ServerConfig> {
         let mut config = Config::default();
-        config.set_default("bind_addresses", vec!["0.0.0.0:5353"])?;
         config.merge(config::File::with_name(path.as_ref()))?;
 
-        Ok(config.try_into()?)
+        let mut config: ServerConfig = config.try_into()?;
+        if config.bind_addresses.is_empty() {
+            config.bind_addresses = default_bind_addresses();
+        }
+
+        Ok(config)
     }
-}
\ No newline at end of file
+}
@@ -1,27 +1,42 @@
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::future::Future;
 use std::net::SocketAddr;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Result};
-use tokio::net::UdpSocket;
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::sync::RwLock;
 
 use trust_dns_client::client::{AsyncClient, ClientHandle};
-use trust_dns_client::op::{Edns, Message};
-use trust_dns_client::rr::Record;
+use trust_dns_client::https::HttpsClientStreamBuilder;
+use trust_dns_client::op::{Edns, Message, ResponseCode};
+use trust_dns_client::rr::{DNSClass, Name, RData, Record, RecordType};
 use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable};
-use trust_dns_client::udp::UdpClientStream;
+use trust_dns_client::tls::tls_client_connect_with_config;
 
 use trust_dns_server::authority::{MessageRequest, MessageResponse, MessageResponseBuilder};
 use trust_dns_server::server::{Request, RequestHandler, ResponseHandler};
 
 use async_trait::async_trait;
 
-use tracing::{info_span, instrument, Instrument};
+use tracing::{info, info_span, instrument, Instrument};
+
+use crate::cache::Cache;
+use crate::multiplex::MultiplexedClient;
 
 type RecordBoxedIter<'a> = Box<dyn Iterator<Item = &'a Record> + Send + 'a>;
+
+tokio::task_local! {
+    /// Set by whichever handler actually produced the response (an upstream
+    /// on a forward, `CacheHandler` on a hit) so `handle_request` can log
+    /// who served a reply without threading it through every return type.
+    static SERVED_BY: std::cell::RefCell<Option<String>>;
+}
+
 #[async_trait]
 pub trait AsyncQueryHandler: Debug + Send + Sync + 'static {
     fn with_next(self: Box<Self>, next: Box<dyn AsyncQueryHandler>) -> Box<dyn AsyncQueryHandler>;
@@ -72,6 +87,7 @@ impl TryIntoMessage for MessageRequest {
 pub struct Upstream {
     upstream: SocketAddr,
     timeout: Duration,
+    client: Arc<RwLock<Option<MultiplexedClient>>>,
     next: Option<Box<dyn AsyncQueryHandler>>,
 }
 
@@ -80,9 +96,27 @@ impl Upstream {
         Upstream {
             upstream,
             timeout,
+            client: Arc::new(RwLock::new(None)),
             next: None,
         }
     }
+
+    /// Returns the shared multiplexed client, connecting lazily on first use
+    /// and reconnecting if a previous attempt left nothing behind.
+    async fn client(&self) -> Result<MultiplexedClient> {
+        if let Some(client) = self.client.read().await.as_ref() {
+            return Ok(client.clone());
+        }
+
+        let mut client = self.client.write().await;
+        if let Some(client) = client.as_ref() {
+            return Ok(client.clone());
+        }
+
+        let connected = MultiplexedClient::connect(self.upstream).await?;
+        *client = Some(connected.clone());
+        Ok(connected)
+    }
 }
 
 #[async_trait]
@@ -101,26 +135,608 @@ impl AsyncQueryHandler for Upstream {
 
     #[instrument(name = "upstream")]
     async fn handle_query(&self, msg: Message) -> Result<Message> {
-        let conn = UdpClientStream::<UdpSocket>::with_timeout(self.upstream, self.timeout);
-        let (mut client, bg) = AsyncClient::connect(conn).await?;
-        tokio::spawn(bg);
         let query = msg
             .queries()
             .first()
             .ok_or(anyhow!("empty queries"))?
             .clone();
-        let resp = client
-            .query(
-                query.name().clone(),
-                query.query_class(),
-                query.query_type(),
-            )
-            .await?;
 
+        let client = self.client().await?;
+        let resp = match tokio::time::timeout(
+            self.timeout,
+            client.query(query.name().clone(), query.query_class(), query.query_type()),
+        )
+        .await
+        {
+            Ok(resp) => resp?,
+            Err(_) => {
+                // The socket may be wedged; drop it so the next query rebuilds it.
+                self.client.write().await.take();
+                return Err(anyhow!("upstream {} timed out", self.upstream));
+            }
+        };
+
+        let _ = SERVED_BY.try_with(|served| *served.borrow_mut() = Some(self.upstream.to_string()));
+        self.next_handler(resp).await
+    }
+}
+
+type CacheKey = (Name, DNSClass, RecordType);
+
+/// Returns the TTL (in seconds) a response should be cached for, or `None`
+/// if it must not be cached.
+///
+/// The TTL is the minimum across all answer records; for answers with no
+/// records (e.g. NXDOMAIN) it falls back to the SOA minimum TTL from the
+/// authority section, and skips caching entirely if neither is present.
+fn cache_ttl(resp: &Message) -> Option<u64> {
+    if let Some(ttl) = resp.answers().iter().map(Record::ttl).min() {
+        return Some(ttl as u64);
+    }
+
+    resp.name_servers().iter().find_map(|record| match record.rdata() {
+        RData::SOA(soa) => Some(soa.minimum() as u64),
+        _ => None,
+    })
+}
+
+/// Subtracts `elapsed_secs` from every answer/authority record's TTL so a
+/// cache hit reports how much life the records actually have left, rather
+/// than the TTL they were cached with.
+fn decrement_ttls(msg: &mut Message, elapsed_secs: u64) {
+    let elapsed = elapsed_secs as u32;
+    for record in msg.answers_mut() {
+        record.set_ttl(record.ttl().saturating_sub(elapsed));
+    }
+    for record in msg.name_servers_mut() {
+        record.set_ttl(record.ttl().saturating_sub(elapsed));
+    }
+}
+
+/// A cached response, paired with the TTL (in seconds) it was cached with so
+/// the elapsed time since insertion can be recovered on a hit.
+type CachedResponse = (Message, u64);
+
+/// Caches upstream responses keyed on `(Name, DNSClass, RecordType)` and
+/// short-circuits the chain on a hit.
+///
+/// The map is shared behind an `Arc<RwLock<_>>` because every per-task clone
+/// of `DnsProxy` shares this one handler, and a background task
+/// periodically calls `Cache::gc` to evict expired entries.
+pub struct CacheHandler {
+    cache: Arc<RwLock<Cache<CacheKey, CachedResponse>>>,
+    next: Option<Box<dyn AsyncQueryHandler>>,
+}
+
+impl Debug for CacheHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CacheHandler").finish()
+    }
+}
+
+impl CacheHandler {
+    pub fn new(gc_interval: Duration) -> Self {
+        let cache = Arc::new(RwLock::new(Cache::new()));
+
+        let gc_cache = cache.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(gc_interval);
+            loop {
+                ticker.tick().await;
+                gc_cache.write().await.gc();
+            }
+        });
+
+        CacheHandler { cache, next: None }
+    }
+}
+
+#[async_trait]
+impl AsyncQueryHandler for CacheHandler {
+    fn with_next(
+        mut self: Box<Self>,
+        next: Box<dyn AsyncQueryHandler>,
+    ) -> Box<dyn AsyncQueryHandler> {
+        self.next = Some(next);
+        self
+    }
+
+    fn next(&self) -> Option<&Box<dyn AsyncQueryHandler>> {
+        self.next.as_ref()
+    }
+
+    #[instrument(name = "cache")]
+    async fn handle_query(&self, msg: Message) -> Result<Message> {
+        let query = msg
+            .queries()
+            .first()
+            .ok_or_else(|| anyhow!("empty queries"))?
+            .clone();
+        let key = (query.name().clone(), query.query_class(), query.query_type());
+        let id = msg.id();
+
+        {
+            let cache = self.cache.read().await;
+            if let Some((cached, ttl)) = cache.get(&key) {
+                let remaining = cache.ttl_remaining(&key).unwrap_or_default();
+                let elapsed = ttl.saturating_sub(remaining.as_secs());
+
+                let mut resp = cached.clone();
+                resp.set_id(id);
+                decrement_ttls(&mut resp, elapsed);
+                let _ =
+                    SERVED_BY.try_with(|served| *served.borrow_mut() = Some("cache".to_string()));
+                return Ok(resp);
+            }
+        }
+
+        let resp = self.next_handler(msg).await?;
+
+        if resp.response_code() != ResponseCode::ServFail {
+            if let Some(ttl) = cache_ttl(&resp) {
+                self.cache
+                    .write()
+                    .await
+                    .insert(key, (resp.clone(), ttl), Duration::from_secs(ttl));
+            }
+        }
+
+        Ok(resp)
+    }
+}
+
+/// Builds a rustls client config trusting the bundled `webpki_roots` plus, if given,
+/// the PEM certificates in `ca_file`, and offering `alpn` as the ALPN
+/// protocol list. Shared by `TlsUpstream` and `HttpsUpstream` so a custom CA
+/// for a private resolver isn't silently dropped on the floor.
+fn build_tls_client_config(ca_file: Option<&str>, alpn: &[String]) -> Result<Arc<rustls::ClientConfig>> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+
+    if let Some(ca_file) = ca_file {
+        let mut reader = std::io::BufReader::new(std::fs::File::open(ca_file)?);
+        for cert in rustls_pemfile::certs(&mut reader)? {
+            roots.add(&rustls::Certificate(cert))?;
+        }
+    }
+
+    let mut config = rustls::ClientConfig::new();
+    config.root_store = roots;
+    config.alpn_protocols = alpn.iter().map(|proto| proto.as_bytes().to_vec()).collect();
+
+    Ok(Arc::new(config))
+}
+
+/// Forwards queries over DNS-over-TLS (:853 by convention).
+///
+/// `tls_dns_name` is the name validated against the upstream's certificate
+/// (the SNI/hostname), which may differ from `upstream`'s bare IP.
+pub struct TlsUpstream {
+    upstream: SocketAddr,
+    tls_dns_name: String,
+    timeout: Duration,
+    client_config: Arc<rustls::ClientConfig>,
+    client: Arc<RwLock<Option<AsyncClient>>>,
+    next: Option<Box<dyn AsyncQueryHandler>>,
+}
+
+impl Debug for TlsUpstream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsUpstream")
+            .field("upstream", &self.upstream)
+            .field("tls_dns_name", &self.tls_dns_name)
+            .finish()
+    }
+}
+
+impl TlsUpstream {
+    /// `ca_file` is an optional PEM bundle trusted in addition to the bundled
+    /// `webpki_roots`; `alpn` is the ALPN protocol list to offer during the handshake.
+    pub fn new(
+        upstream: SocketAddr,
+        tls_dns_name: String,
+        timeout: Duration,
+        ca_file: Option<&str>,
+        alpn: &[String],
+    ) -> Result<Self> {
+        Ok(TlsUpstream {
+            upstream,
+            tls_dns_name,
+            timeout,
+            client_config: build_tls_client_config(ca_file, alpn)?,
+            client: Arc::new(RwLock::new(None)),
+            next: None,
+        })
+    }
+
+    /// Returns the shared client, connecting lazily on first use and
+    /// reconnecting if a previous attempt left nothing behind.
+    async fn client(&self) -> Result<AsyncClient> {
+        if let Some(client) = self.client.read().await.as_ref() {
+            return Ok(client.clone());
+        }
+
+        let mut client = self.client.write().await;
+        if let Some(client) = client.as_ref() {
+            return Ok(client.clone());
+        }
+
+        let (conn, tls_handle) = tls_client_connect_with_config(
+            self.upstream,
+            self.tls_dns_name.clone(),
+            self.client_config.clone(),
+        );
+        let (connected, bg) = match tokio::time::timeout(
+            self.timeout,
+            AsyncClient::with_timeout(conn, tls_handle, self.timeout, None),
+        )
+        .await
+        {
+            Ok(result) => result?,
+            Err(_) => return Err(anyhow!("tls upstream {} timed out connecting", self.upstream)),
+        };
+        tokio::spawn(bg);
+
+        *client = Some(connected.clone());
+        Ok(connected)
+    }
+}
+
+#[async_trait]
+impl AsyncQueryHandler for TlsUpstream {
+    fn with_next(
+        mut self: Box<Self>,
+        next: Box<dyn AsyncQueryHandler>,
+    ) -> Box<dyn AsyncQueryHandler> {
+        self.next = Some(next);
+        self
+    }
+
+    fn next(&self) -> Option<&Box<dyn AsyncQueryHandler>> {
+        self.next.as_ref()
+    }
+
+    #[instrument(name = "tls_upstream")]
+    async fn handle_query(&self, msg: Message) -> Result<Message> {
+        let query = msg
+            .queries()
+            .first()
+            .ok_or_else(|| anyhow!("empty queries"))?
+            .clone();
+
+        let mut client = self.client().await?;
+        let resp = match client
+            .query(query.name().clone(), query.query_class(), query.query_type())
+            .await
+        {
+            Ok(resp) => resp,
+            Err(err) => {
+                // The connection may be wedged; drop it so the next query rebuilds it.
+                self.client.write().await.take();
+                return Err(err.into());
+            }
+        };
+
+        let _ = SERVED_BY.try_with(|served| *served.borrow_mut() = Some(self.upstream.to_string()));
         self.next_handler(resp.into()).await
     }
 }
 
+/// Forwards queries over DNS-over-HTTPS, e.g. `https://cloudflare-dns.com/dns-query`.
+pub struct HttpsUpstream {
+    upstream: SocketAddr,
+    tls_dns_name: String,
+    path: String,
+    timeout: Duration,
+    client_config: Arc<rustls::ClientConfig>,
+    client: Arc<RwLock<Option<AsyncClient>>>,
+    next: Option<Box<dyn AsyncQueryHandler>>,
+}
+
+impl Debug for HttpsUpstream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpsUpstream")
+            .field("upstream", &self.upstream)
+            .field("tls_dns_name", &self.tls_dns_name)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl HttpsUpstream {
+    /// `ca_file` is an optional PEM bundle trusted in addition to the bundled
+    /// `webpki_roots`; `alpn` is the ALPN protocol list to offer during the handshake.
+    pub fn new(
+        upstream: SocketAddr,
+        tls_dns_name: String,
+        path: String,
+        timeout: Duration,
+        ca_file: Option<&str>,
+        alpn: &[String],
+    ) -> Result<Self> {
+        Ok(HttpsUpstream {
+            upstream,
+            tls_dns_name,
+            path,
+            timeout,
+            client_config: build_tls_client_config(ca_file, alpn)?,
+            client: Arc::new(RwLock::new(None)),
+            next: None,
+        })
+    }
+
+    /// Returns the shared client, connecting lazily on first use and
+    /// reconnecting if a previous attempt left nothing behind.
+    async fn client(&self) -> Result<AsyncClient> {
+        if let Some(client) = self.client.read().await.as_ref() {
+            return Ok(client.clone());
+        }
+
+        let mut client = self.client.write().await;
+        if let Some(client) = client.as_ref() {
+            return Ok(client.clone());
+        }
+
+        let conn = HttpsClientStreamBuilder::with_client_config(self.client_config.clone()).build(
+            self.upstream,
+            self.tls_dns_name.clone(),
+            self.path.clone(),
+        );
+        let (connected, bg) = match tokio::time::timeout(self.timeout, AsyncClient::connect(conn)).await {
+            Ok(result) => result?,
+            Err(_) => return Err(anyhow!("https upstream {} timed out connecting", self.upstream)),
+        };
+        tokio::spawn(bg);
+
+        *client = Some(connected.clone());
+        Ok(connected)
+    }
+}
+
+#[async_trait]
+impl AsyncQueryHandler for HttpsUpstream {
+    fn with_next(
+        mut self: Box<Self>,
+        next: Box<dyn AsyncQueryHandler>,
+    ) -> Box<dyn AsyncQueryHandler> {
+        self.next = Some(next);
+        self
+    }
+
+    fn next(&self) -> Option<&Box<dyn AsyncQueryHandler>> {
+        self.next.as_ref()
+    }
+
+    #[instrument(name = "https_upstream")]
+    async fn handle_query(&self, msg: Message) -> Result<Message> {
+        let query = msg
+            .queries()
+            .first()
+            .ok_or_else(|| anyhow!("empty queries"))?
+            .clone();
+
+        let mut client = self.client().await?;
+        let resp = match client
+            .query(query.name().clone(), query.query_class(), query.query_type())
+            .await
+        {
+            Ok(resp) => resp,
+            Err(err) => {
+                // The connection may be wedged; drop it so the next query rebuilds it.
+                self.client.write().await.take();
+                return Err(err.into());
+            }
+        };
+
+        let _ = SERVED_BY.try_with(|served| *served.borrow_mut() = Some(self.upstream.to_string()));
+        self.next_handler(resp.into()).await
+    }
+}
+
+type RuleId = usize;
+
+/// Routes by longest domain-suffix match to one of several downstream
+/// handler chains instead of always forwarding to a single default
+/// upstream, enabling split-horizon / conditional forwarding (e.g.
+/// `internal.corp` to a corp resolver, `cn` to a resolver in-region).
+pub struct RouterHandler {
+    /// Suffixes checked most-specific-first (most labels first), so
+    /// `example.internal.corp` matches `internal.corp` before a shorter,
+    /// less specific suffix would.
+    rules: Vec<(Name, RuleId)>,
+    routes: HashMap<RuleId, Arc<dyn AsyncQueryHandler>>,
+    default: Arc<dyn AsyncQueryHandler>,
+    next: Option<Box<dyn AsyncQueryHandler>>,
+}
+
+impl Debug for RouterHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RouterHandler")
+            .field(
+                "rules",
+                &self
+                    .rules
+                    .iter()
+                    .map(|(suffix, _)| suffix.to_string())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl RouterHandler {
+    /// `rules` pairs a domain suffix with the handler chain that should
+    /// serve queries under it; `default` serves everything else.
+    pub fn new(rules: Vec<(Name, Box<dyn AsyncQueryHandler>)>, default: Box<dyn AsyncQueryHandler>) -> Self {
+        let mut rules: Vec<(Name, RuleId, Arc<dyn AsyncQueryHandler>)> = rules
+            .into_iter()
+            .enumerate()
+            .map(|(id, (suffix, handler))| (suffix, id, Arc::from(handler)))
+            .collect();
+        rules.sort_by(|a, b| b.0.num_labels().cmp(&a.0.num_labels()));
+
+        let mut order = Vec::with_capacity(rules.len());
+        let mut routes = HashMap::with_capacity(rules.len());
+        for (suffix, id, handler) in rules {
+            order.push((suffix, id));
+            routes.insert(id, handler);
+        }
+
+        RouterHandler {
+            rules: order,
+            routes,
+            default: Arc::from(default),
+            next: None,
+        }
+    }
+
+    fn route(&self, name: &Name) -> &Arc<dyn AsyncQueryHandler> {
+        self.rules
+            .iter()
+            .find(|(suffix, _)| suffix.zone_of(name))
+            .map(|(_, id)| &self.routes[id])
+            .unwrap_or(&self.default)
+    }
+}
+
+#[async_trait]
+impl AsyncQueryHandler for RouterHandler {
+    fn with_next(
+        mut self: Box<Self>,
+        next: Box<dyn AsyncQueryHandler>,
+    ) -> Box<dyn AsyncQueryHandler> {
+        self.next = Some(next);
+        self
+    }
+
+    fn next(&self) -> Option<&Box<dyn AsyncQueryHandler>> {
+        self.next.as_ref()
+    }
+
+    #[instrument(name = "router")]
+    async fn handle_query(&self, msg: Message) -> Result<Message> {
+        let name = msg
+            .queries()
+            .first()
+            .ok_or_else(|| anyhow!("empty queries"))?
+            .name()
+            .clone();
+
+        let resp = self.route(&name).clone().handle_query(msg).await?;
+        self.next_handler(resp).await
+    }
+}
+
+/// Queries several upstreams one at a time, in order, returning the first
+/// successful, non-SERVFAIL response. Unlike chaining the upstreams via
+/// `with_next` (which would feed each upstream's *response* as the next
+/// query), every upstream here is tried against the same original query.
+#[derive(Debug)]
+pub struct SequentialHandler {
+    children: Vec<Arc<dyn AsyncQueryHandler>>,
+    next: Option<Box<dyn AsyncQueryHandler>>,
+}
+
+impl SequentialHandler {
+    pub fn new(children: Vec<Box<dyn AsyncQueryHandler>>) -> Self {
+        SequentialHandler {
+            children: children.into_iter().map(Arc::from).collect(),
+            next: None,
+        }
+    }
+}
+
+#[async_trait]
+impl AsyncQueryHandler for SequentialHandler {
+    fn with_next(
+        mut self: Box<Self>,
+        next: Box<dyn AsyncQueryHandler>,
+    ) -> Box<dyn AsyncQueryHandler> {
+        self.next = Some(next);
+        self
+    }
+
+    fn next(&self) -> Option<&Box<dyn AsyncQueryHandler>> {
+        self.next.as_ref()
+    }
+
+    #[instrument(name = "sequential")]
+    async fn handle_query(&self, msg: Message) -> Result<Message> {
+        let mut last_err = anyhow!("sequential: no upstreams configured");
+        for child in &self.children {
+            match child.handle_query(msg.clone()).await {
+                Ok(resp) if resp.response_code() != ResponseCode::ServFail => {
+                    return self.next_handler(resp).await;
+                }
+                Ok(resp) => last_err = anyhow!("upstream returned {:?}", resp.response_code()),
+                Err(err) => last_err = err,
+            }
+        }
+
+        Err(last_err)
+    }
+}
+
+/// Queries several upstreams concurrently and returns the first successful,
+/// non-SERVFAIL response, cutting tail latency when one resolver is slow or
+/// flaky. The losing queries are dropped (and, with them, their futures)
+/// once a winner is picked.
+#[derive(Debug)]
+pub struct RaceHandler {
+    children: Vec<Arc<dyn AsyncQueryHandler>>,
+    next: Option<Box<dyn AsyncQueryHandler>>,
+}
+
+impl RaceHandler {
+    pub fn new(children: Vec<Box<dyn AsyncQueryHandler>>) -> Self {
+        RaceHandler {
+            children: children.into_iter().map(Arc::from).collect(),
+            next: None,
+        }
+    }
+}
+
+#[async_trait]
+impl AsyncQueryHandler for RaceHandler {
+    fn with_next(
+        mut self: Box<Self>,
+        next: Box<dyn AsyncQueryHandler>,
+    ) -> Box<dyn AsyncQueryHandler> {
+        self.next = Some(next);
+        self
+    }
+
+    fn next(&self) -> Option<&Box<dyn AsyncQueryHandler>> {
+        self.next.as_ref()
+    }
+
+    #[instrument(name = "race")]
+    async fn handle_query(&self, msg: Message) -> Result<Message> {
+        let mut races: FuturesUnordered<_> = self
+            .children
+            .iter()
+            .cloned()
+            .map(|child| {
+                let msg = msg.clone();
+                async move { child.handle_query(msg).await }
+            })
+            .collect();
+
+        let mut last_err = anyhow!("race: no upstreams configured");
+        while let Some(result) = races.next().await {
+            match result {
+                Ok(resp) if resp.response_code() != ResponseCode::ServFail => {
+                    return self.next_handler(resp).await;
+                }
+                Ok(resp) => last_err = anyhow!("upstream returned {:?}", resp.response_code()),
+                Err(err) => last_err = err,
+            }
+        }
+
+        Err(last_err)
+    }
+}
+
 fn make_response_builder(req: &Request) -> MessageResponseBuilder<'_> {
     let message = &req.message;
     let queries = message.raw_queries();
@@ -159,12 +775,14 @@ fn make_forward_response<'q, 'a>(req: &'q Request, resp: &'a Message) -> Message
 
 /// This macro trys to handle the `Result` type in `RequestHandler::handle_request`,
 /// if the `result` is `Ok(T)`, then the inner value is extracted,
-/// otherwise, an error message is sent via `handle` and return from the handler.
+/// otherwise an error event is logged, an error message is sent via `handle`,
+/// and the handler returns.
 macro_rules! try_handle {
     (request = $request:expr, handle = $handle:expr, result = $expr:expr $(,)?) => {
         match $expr {
             std::result::Result::Ok(val) => val,
-            std::result::Result::Err(_) => {
+            std::result::Result::Err(err) => {
+                tracing::error!(error = %err, "failed to handle request");
                 let _ = $handle.send_response(make_err_msg_response(&$request));
                 return;
             }
@@ -172,6 +790,8 @@ macro_rules! try_handle {
     };
 }
 
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(0);
+
 impl RequestHandler for DnsProxy {
     type ResponseFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
     fn handle_request<R: ResponseHandler>(
@@ -180,21 +800,55 @@ impl RequestHandler for DnsProxy {
         mut response_handle: R,
     ) -> Self::ResponseFuture {
         let clone = self.clone();
-        Box::pin(
+        let request_id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+        let client_addr = request.src;
+        let start = Instant::now();
+
+        let span = info_span!(
+            "handle_request",
+            request_id,
+            %client_addr,
+            name = tracing::field::Empty,
+            record_type = tracing::field::Empty,
+        );
+
+        Box::pin(SERVED_BY.scope(
+            std::cell::RefCell::new(None),
             (async move {
                 let message = try_handle!(
                     request = request,
                     handle = response_handle,
                     result = request.message.try_into_message()
                 );
+
+                if let Some(query) = message.queries().first() {
+                    tracing::Span::current().record("name", &query.name().to_string().as_str());
+                    tracing::Span::current()
+                        .record("record_type", &tracing::field::debug(&query.query_type()));
+                }
+
                 let resp = try_handle!(
                     request = request,
                     handle = response_handle,
                     result = clone.handlers.handle_query(message).await
                 );
+
+                let served_by = SERVED_BY
+                    .try_with(|served| served.borrow().clone())
+                    .ok()
+                    .flatten();
+
+                info!(
+                    rcode = ?resp.response_code(),
+                    answer_count = resp.header().answer_count(),
+                    elapsed_ms = start.elapsed().as_millis() as u64,
+                    served_by = served_by.as_deref().unwrap_or("unknown"),
+                    "handled request"
+                );
+
                 let _ = response_handle.send_response(make_forward_response(&request, &resp));
             })
-            .instrument(info_span!("handle_request")),
-        )
+            .instrument(span),
+        ))
     }
 }
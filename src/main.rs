@@ -1,20 +1,26 @@
 use std::net::SocketAddr;
 use std::time::Duration;
 
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, UdpSocket};
 use trust_dns_server::ServerFuture;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 use clap::{AppSettings, Clap};
 
+use trust_dns_client::rr::Name;
+
 mod cache;
 mod config;
 mod handler;
+mod multiplex;
 mod trace;
 
-use crate::config::ServerConfig;
-use crate::handler::{DnsProxy, Upstream};
+use crate::config::{BindProtocol, RouteRule, ServerConfig, UpstreamConfig, UpstreamKind, UpstreamsMode};
+use crate::handler::{
+    AsyncQueryHandler, CacheHandler, DnsProxy, HttpsUpstream, RaceHandler, RouterHandler,
+    SequentialHandler, TlsUpstream, Upstream,
+};
 
 #[derive(Clap, Debug)]
 #[clap(version = "0.1.0", author = "iosmanthus. <myosmanthustree@gmail.com>")]
@@ -24,23 +30,132 @@ struct Opts {
     config: String,
 }
 
+const DEFAULT_UPSTREAM_TIMEOUT: Duration = Duration::from_secs(5);
+const CACHE_GC_INTERVAL: Duration = Duration::from_secs(60);
+
+fn build_upstream(upstream: &UpstreamConfig) -> Result<Box<dyn AsyncQueryHandler>> {
+    let addr = upstream.addr.parse()?;
+    let alpn = upstream.alpn.clone().unwrap_or_default();
+    Ok(match upstream.kind {
+        UpstreamKind::Udp => Box::new(Upstream::new(addr, DEFAULT_UPSTREAM_TIMEOUT)),
+        UpstreamKind::Tls => {
+            let tls_name = upstream
+                .tls_name
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("tls upstream requires `tls_name`"))?;
+            Box::new(TlsUpstream::new(
+                addr,
+                tls_name,
+                DEFAULT_UPSTREAM_TIMEOUT,
+                upstream.ca_file.as_deref(),
+                &alpn,
+            )?)
+        }
+        UpstreamKind::Https => {
+            let tls_name = upstream
+                .tls_name
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("https upstream requires `tls_name`"))?;
+            let path = upstream.doh_path.clone().unwrap_or_else(|| "/dns-query".to_string());
+            Box::new(HttpsUpstream::new(
+                addr,
+                tls_name,
+                path,
+                DEFAULT_UPSTREAM_TIMEOUT,
+                upstream.ca_file.as_deref(),
+                &alpn,
+            )?)
+        }
+    })
+}
+
+/// Folds a list of handlers into a single chain, head first: each handler's
+/// `next` is the one after it in the list.
+fn chain(handlers: Vec<Box<dyn AsyncQueryHandler>>) -> Result<Box<dyn AsyncQueryHandler>> {
+    let mut first = None;
+    for handler in handlers.into_iter().rev() {
+        first = match first {
+            None => Some(handler),
+            Some(head) => Some(handler.with_next(head)),
+        }
+    }
+    first.ok_or_else(|| anyhow!("empty handlers chain"))
+}
+
+fn build_default_chain(config: &ServerConfig) -> Result<Box<dyn AsyncQueryHandler>> {
+    let upstreams = if config.upstreams.is_empty() {
+        vec![Box::new(Upstream::new(
+            "1.1.1.1:53".parse()?,
+            DEFAULT_UPSTREAM_TIMEOUT,
+        )) as Box<dyn AsyncQueryHandler>]
+    } else {
+        config
+            .upstreams
+            .iter()
+            .map(build_upstream)
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    let upstream_chain: Box<dyn AsyncQueryHandler> = if upstreams.len() > 1 {
+        match config.mode {
+            UpstreamsMode::Race => Box::new(RaceHandler::new(upstreams)),
+            UpstreamsMode::Sequential => Box::new(SequentialHandler::new(upstreams)),
+        }
+    } else {
+        chain(upstreams)?
+    };
+
+    chain(vec![
+        Box::new(CacheHandler::new(CACHE_GC_INTERVAL)),
+        upstream_chain,
+    ])
+}
+
+fn build_router(routes: &[RouteRule], default: Box<dyn AsyncQueryHandler>) -> Result<Box<dyn AsyncQueryHandler>> {
+    let rules = routes
+        .iter()
+        .map(|rule| {
+            let cached = chain(vec![
+                Box::new(CacheHandler::new(CACHE_GC_INTERVAL)),
+                build_upstream(&rule.upstream)?,
+            ])?;
+            Ok((Name::from_ascii(&rule.suffix)?, cached))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Box::new(RouterHandler::new(rules, default)))
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let opts = Opts::parse();
     let config = ServerConfig::new(&opts.config)?;
 
-    trace::init(config.trace)?;
+    trace::init(config.trace.clone())?;
+
+    let default_chain = build_default_chain(&config)?;
+    let top_handler = if config.routes.is_empty() {
+        default_chain
+    } else {
+        build_router(&config.routes, default_chain)?
+    };
 
-    let mut server = ServerFuture::new(DnsProxy::new(vec![Box::new(Upstream::new(
-        "1.1.1.1:53".parse()?,
-        Duration::from_secs(5),
-    ))])?);
+    let mut server = ServerFuture::new(DnsProxy::new(vec![top_handler])?);
 
-    for addr in config.bind_addresses.iter() {
-        server.register_listener(
-            TcpListener::bind(addr.parse::<SocketAddr>()?).await?,
-            Duration::from_secs(10),
-        );
+    for bind in config.bind_addresses.iter() {
+        let addr: SocketAddr = bind.addr.parse()?;
+        let timeout = Duration::from_secs(bind.timeout_secs);
+        match bind.protocol {
+            BindProtocol::Udp => {
+                server.register_socket(UdpSocket::bind(addr).await?, timeout);
+            }
+            BindProtocol::Tcp => {
+                server.register_listener(TcpListener::bind(addr).await?, timeout);
+            }
+            BindProtocol::Tls => {
+                return Err(anyhow!("tls listeners are not supported yet"));
+            }
+        }
     }
     server.block_until_done().await?;
     Ok(())
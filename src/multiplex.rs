@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use rand::random;
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, oneshot};
+
+use trust_dns_client::op::{Message, MessageType, OpCode, Query};
+use trust_dns_client::rr::{DNSClass, Name, RecordType};
+use trust_dns_client::serialize::binary::{BinDecodable, BinEncodable};
+
+/// Upper bound on concurrently in-flight queries, so a stalled upstream
+/// can't grow the pending-response table without bound.
+const MAX_IN_FLIGHT: usize = 100;
+
+const MAX_DATAGRAM_SIZE: usize = 4096;
+
+/// How long a query may sit in `pending` without a reply before it's swept
+/// out, so a caller that never gets an answer (e.g. the upstream silently
+/// drops the datagram) doesn't hold its slot forever.
+const PENDING_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often `dispatch` sweeps `pending` for entries past `PENDING_QUERY_TIMEOUT`.
+const PENDING_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+enum Command {
+    Query(Message, oneshot::Sender<Result<Message>>),
+}
+
+/// A cheaply-`Clone`-able handle to a single, long-lived UDP socket shared
+/// by every in-flight query to one upstream.
+///
+/// Mirrors trust-dns's `DnsMultiplexer`/`ClientHandle`: a dispatch task owns
+/// the socket, matches responses to callers by the 16-bit DNS message id,
+/// and rebuilds the socket if it ever errors out from under it.
+#[derive(Clone)]
+pub struct MultiplexedClient {
+    commands: mpsc::Sender<Command>,
+}
+
+impl std::fmt::Debug for MultiplexedClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MultiplexedClient").finish()
+    }
+}
+
+impl MultiplexedClient {
+    pub async fn connect(upstream: SocketAddr) -> Result<Self> {
+        let socket = connect_socket(upstream).await?;
+        let (commands, receiver) = mpsc::channel(MAX_IN_FLIGHT);
+        tokio::spawn(dispatch(upstream, socket, receiver));
+        Ok(MultiplexedClient { commands })
+    }
+
+    pub async fn query(&self, name: Name, class: DNSClass, query_type: RecordType) -> Result<Message> {
+        let mut query = Query::new();
+        query.set_name(name).set_query_class(class).set_query_type(query_type);
+
+        let mut msg = Message::new();
+        msg.set_message_type(MessageType::Query);
+        msg.set_op_code(OpCode::Query);
+        msg.set_recursion_desired(true);
+        msg.add_query(query);
+
+        let (tx, rx) = oneshot::channel();
+        self.commands
+            .clone()
+            .send(Command::Query(msg, tx))
+            .await
+            .map_err(|_| anyhow!("upstream dispatcher has shut down"))?;
+        rx.await
+            .map_err(|_| anyhow!("upstream dispatcher dropped the response"))?
+    }
+}
+
+async fn connect_socket(upstream: SocketAddr) -> Result<UdpSocket> {
+    let bind_addr: SocketAddr = if upstream.is_ipv4() {
+        "0.0.0.0:0".parse()?
+    } else {
+        "[::]:0".parse()?
+    };
+    let socket = UdpSocket::bind(bind_addr).await?;
+    socket.connect(upstream).await?;
+    Ok(socket)
+}
+
+/// Owns the socket and the table of callers awaiting a reply; runs for the
+/// lifetime of the `MultiplexedClient`.
+async fn dispatch(upstream: SocketAddr, mut socket: UdpSocket, mut commands: mpsc::Receiver<Command>) {
+    let mut pending: HashMap<u16, (oneshot::Sender<Result<Message>>, Instant)> = HashMap::new();
+    let mut buf = [0u8; MAX_DATAGRAM_SIZE];
+    let mut sweep = tokio::time::interval(PENDING_SWEEP_INTERVAL);
+
+    loop {
+        tokio::select! {
+            command = commands.recv() => {
+                let Command::Query(mut msg, tx) = match command {
+                    Some(command) => command,
+                    None => return,
+                };
+
+                if pending.len() >= MAX_IN_FLIGHT {
+                    let _ = tx.send(Err(anyhow!("too many in-flight queries to {}", upstream)));
+                    continue;
+                }
+
+                let id = loop {
+                    let id = random::<u16>();
+                    if !pending.contains_key(&id) {
+                        break id;
+                    }
+                };
+                msg.set_id(id);
+
+                let bytes = match msg.to_bytes() {
+                    Ok(bytes) => bytes,
+                    Err(err) => {
+                        let _ = tx.send(Err(err.into()));
+                        continue;
+                    }
+                };
+
+                if let Err(err) = socket.send(&bytes).await {
+                    let _ = tx.send(Err(err.into()));
+                    if let Ok(reconnected) = connect_socket(upstream).await {
+                        socket = reconnected;
+                    }
+                    continue;
+                }
+
+                pending.insert(id, (tx, Instant::now() + PENDING_QUERY_TIMEOUT));
+            }
+            received = socket.recv(&mut buf) => {
+                match received {
+                    Ok(len) => {
+                        if let Ok(resp) = Message::from_bytes(&buf[..len]) {
+                            if let Some((tx, _)) = pending.remove(&resp.id()) {
+                                let _ = tx.send(Ok(resp));
+                            }
+                        }
+                    }
+                    Err(_) => {
+                        if let Ok(reconnected) = connect_socket(upstream).await {
+                            socket = reconnected;
+                        }
+                    }
+                }
+            }
+            _ = sweep.tick() => {
+                let now = Instant::now();
+                pending.retain(|_, (_, deadline)| *deadline > now);
+            }
+        }
+    }
+}